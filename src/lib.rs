@@ -2,17 +2,93 @@
 //! with either `new` or `const_new`
 use anyhow::{bail, Result};
 use compact_str::CompactString;
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt, io};
 
 #[cfg(test)]
 mod test;
 
+/// The kind of malformed escape sequence encountered by [`Escape::try_unescape`]
+/// and [`Escape::try_unescape_to`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnescapeErrorKind {
+    /// the escape char was the last character in the input
+    LoneEscapeChar,
+    /// a `u` escape was not followed by `{`
+    NoBraceInUnicodeEscape,
+    /// a `u{` unicode escape had no matching `}`, or a fixed-width unicode
+    /// escape ended before all of its hex digits
+    UnclosedUnicodeEscape,
+    /// a `u{}` unicode escape contained no hex digits
+    EmptyUnicodeEscape,
+    /// a unicode escape contained a character that is not a hex digit
+    InvalidCharInUnicodeEscape,
+    /// the unicode escape's value was not a valid code point
+    OutOfRangeUnicodeEscape,
+    /// the unicode escape's value was a lone utf-16 surrogate (0xD800..=0xDFFF)
+    LoneSurrogateUnicodeEscape,
+}
+
+/// An error produced when the input to [`Escape::try_unescape`] or
+/// [`Escape::try_unescape_to`] contains a malformed escape sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnescapeError {
+    /// the byte offset in the input at which the malformed escape sequence begins
+    pub pos: usize,
+    /// the kind of malformed escape sequence that was found
+    pub kind: UnescapeErrorKind,
+}
+
+impl fmt::Display for UnescapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self.kind {
+            UnescapeErrorKind::LoneEscapeChar => "escape char at end of input",
+            UnescapeErrorKind::NoBraceInUnicodeEscape => "unicode escape not followed by `{`",
+            UnescapeErrorKind::UnclosedUnicodeEscape => "unicode escape missing closing `}`",
+            UnescapeErrorKind::EmptyUnicodeEscape => "unicode escape has no hex digits",
+            UnescapeErrorKind::InvalidCharInUnicodeEscape => {
+                "unicode escape contains a non hex digit"
+            }
+            UnescapeErrorKind::OutOfRangeUnicodeEscape => {
+                "unicode escape is not a valid code point"
+            }
+            UnescapeErrorKind::LoneSurrogateUnicodeEscape => {
+                "unicode escape is a lone utf-16 surrogate"
+            }
+        };
+        write!(f, "{msg} at byte {}", self.pos)
+    }
+}
+
+impl std::error::Error for UnescapeError {}
+
+/// The textual form used for unicode escape sequences produced by `escape`
+/// and `escape_to`, and recognized by `unescape`/`unescape_to` and their
+/// fallible counterparts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnicodeFormat {
+    /// the Rust-style braced form, e.g. `\u{1f600}`
+    #[default]
+    Braced,
+    /// a fixed 4 hex digit form, e.g. the escape char followed by `u0041`
+    /// for `A`. Code points above
+    /// U+FFFF are zero padded to at least 4 digits rather than split into a
+    /// surrogate pair, so this format should only be used when every escaped
+    /// code point is known to be in the BMP.
+    Fixed4,
+    /// a fixed 8 hex digit form, e.g. `\U0001F600`, as used by TOML
+    Fixed8,
+    /// a fixed 4 hex digit form that represents code points above U+FFFF as a
+    /// utf-16 surrogate pair, as used by JSON
+    Fixed4WithSurrogatePairs,
+}
+
 #[derive(Debug, Clone)]
 pub struct Escape {
     escape_char: char,
     escape: Box<[char]>,
     tr: Box<[(char, CompactString)]>,
     generic: Option<fn(char) -> bool>,
+    unicode_format: UnicodeFormat,
 }
 
 fn is_sep(esc: &mut bool, escape_char: char, c: char, sep: char) -> bool {
@@ -24,6 +100,366 @@ fn is_sep(esc: &mut bool, escape_char: char, c: char, sep: char) -> bool {
     }
 }
 
+/// A small queue of pending chars, used by [`EscapeIter`] to hold the escape
+/// char and the translation or `u{...}` expansion it owes the caller before
+/// it can advance the source iterator. Translation targets and unicode
+/// escape sequences are usually short, so this rarely grows past a handful
+/// of chars, but `Escape::new` places no upper bound on translation target
+/// length, so this has to tolerate an arbitrarily long one without
+/// overflowing or corrupting output.
+#[derive(Debug, Clone, Default)]
+struct PendingChars(std::collections::VecDeque<char>);
+
+impl PendingChars {
+    fn push_back(&mut self, c: char) {
+        self.0.push_back(c);
+    }
+
+    fn extend(&mut self, it: impl Iterator<Item = char>) {
+        self.0.extend(it);
+    }
+
+    fn pop_front(&mut self) -> Option<char> {
+        self.0.pop_front()
+    }
+}
+
+/// Iterator returned by [`Escape::escape_iter`]; lazily yields the escaped
+/// form of its input one char at a time without allocating a `String`.
+struct EscapeIter<'e, 'a> {
+    esc: &'e Escape,
+    chars: std::str::Chars<'a>,
+    pending: PendingChars,
+}
+
+impl<'e, 'a> Iterator for EscapeIter<'e, 'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if let Some(c) = self.pending.pop_front() {
+            return Some(c);
+        }
+        let c = self.chars.next()?;
+        if self.esc.escape.contains(&c) {
+            match self
+                .esc
+                .tr
+                .iter()
+                .find_map(|(k, e)| if *k == c { Some(e) } else { None })
+            {
+                Some(e) => self.pending.extend(e.chars()),
+                None => self.pending.push_back(c),
+            }
+            Some(self.esc.escape_char)
+        } else if self.esc.generic.as_ref().is_some_and(|g| g(c)) {
+            self.esc.push_unicode_escape(&mut self.pending, c as u32);
+            self.pending.pop_front()
+        } else {
+            Some(c)
+        }
+    }
+}
+
+/// Iterator returned by [`Escape::unescape_iter`]; lazily yields the
+/// unescaped form of its input one char at a time without allocating a
+/// `String`. `rest` plays the role that the `escaped`/`skip_to` locals play
+/// in [`Escape::try_unescape_to`]: it always points at the next unconsumed byte,
+/// so "an escape is pending" is just "`rest` starts with the escape char" and
+/// "skip to byte N" is just "`rest` has already been advanced past N".
+struct UnescapeIter<'e, 'a> {
+    esc: &'e Escape,
+    rest: &'a str,
+}
+
+impl<'e, 'a> Iterator for UnescapeIter<'e, 'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        loop {
+            let mut chars = self.rest.chars();
+            let c = chars.next()?;
+            if c != self.esc.escape_char {
+                self.rest = chars.as_str();
+                return Some(c);
+            }
+            let after = chars.as_str();
+            if after.is_empty() {
+                // a lone escape char at the end of input is silently dropped,
+                // matching `unescape_to`'s lossy handling of garbage input
+                self.rest = after;
+                continue;
+            }
+            if let Some((v, k)) = self.esc.tr.iter().find(|(_, k)| after.starts_with(k.as_str()))
+            {
+                self.rest = &after[k.len()..];
+                return Some(*v);
+            }
+            if let Some((len, ch)) = self.esc.parse_unicode_escape_seq(after) {
+                self.rest = &after[len..];
+                return Some(ch);
+            }
+            let mut after_chars = after.chars();
+            let literal = after_chars.next().expect("after is non-empty");
+            self.rest = after_chars.as_str();
+            return Some(literal);
+        }
+    }
+}
+
+/// Iterator returned by [`Escape::split_unescaped`] and
+/// [`Escape::splitn_unescaped`]; scans for an unescaped `sep` and unescapes
+/// each field in the same pass, the way [`UnescapeIter`] unescapes a whole
+/// string in one pass. A field is only copied into an owned `String` once an
+/// escape char actually turns up in it; fields with nothing to unescape are
+/// yielded as a borrow of the original input, same as [`Escape::unescape`].
+struct SplitUnescapedIter<'e, 'a> {
+    esc: &'e Escape,
+    rest: &'a str,
+    sep: char,
+    // `None` means unbounded (`split_unescaped`); `Some(n)` means at most `n`
+    // more fields, with the last one taking the rest of the string verbatim
+    // (unescaped, but not split further), matching `str::splitn`.
+    remaining: Option<usize>,
+    finished: bool,
+}
+
+impl<'e, 'a> SplitUnescapedIter<'e, 'a> {
+    /// scan `self.rest` for the next field, stopping at an unescaped `sep`
+    /// when `stop_at_sep` is true, or consuming the whole rest of the input
+    /// as one field otherwise
+    fn next_field(&mut self, stop_at_sep: bool) -> Option<Cow<'a, str>> {
+        let input = self.rest;
+        let mut owned: Option<String> = None;
+        let mut scan = input;
+        loop {
+            let mut chars = scan.chars();
+            let Some(c) = chars.next() else {
+                self.finished = true;
+                self.rest = "";
+                let field = &input[..input.len() - scan.len()];
+                return Some(owned.map_or(Cow::Borrowed(field), Cow::Owned));
+            };
+            if c == self.esc.escape_char {
+                let consumed = input.len() - scan.len();
+                let buf = owned.get_or_insert_with(|| input[..consumed].to_string());
+                let after = chars.as_str();
+                if after.is_empty() {
+                    // a lone escape char at the end of input is silently
+                    // dropped, matching `UnescapeIter`
+                    scan = after;
+                    continue;
+                }
+                if let Some((v, k)) = self.esc.tr.iter().find(|(_, k)| after.starts_with(k.as_str()))
+                {
+                    buf.push(*v);
+                    scan = &after[k.len()..];
+                    continue;
+                }
+                if let Some((len, ch)) = self.esc.parse_unicode_escape_seq(after) {
+                    buf.push(ch);
+                    scan = &after[len..];
+                    continue;
+                }
+                let mut after_chars = after.chars();
+                let literal = after_chars.next().expect("after is non-empty");
+                buf.push(literal);
+                scan = after_chars.as_str();
+                continue;
+            } else if stop_at_sep && c == self.sep {
+                let field = &input[..input.len() - scan.len()];
+                self.rest = chars.as_str();
+                return Some(owned.map_or(Cow::Borrowed(field), Cow::Owned));
+            } else {
+                if let Some(buf) = owned.as_mut() {
+                    buf.push(c);
+                }
+                scan = chars.as_str();
+                continue;
+            }
+        }
+    }
+}
+
+impl<'e, 'a> Iterator for SplitUnescapedIter<'e, 'a> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        if self.finished {
+            return None;
+        }
+        match self.remaining {
+            Some(0) => {
+                self.finished = true;
+                None
+            }
+            Some(1) => {
+                self.remaining = Some(0);
+                self.next_field(false)
+            }
+            Some(n) => {
+                let field = self.next_field(true);
+                if field.is_some() {
+                    self.remaining = Some(n - 1);
+                }
+                field
+            }
+            None => self.next_field(true),
+        }
+    }
+}
+
+/// [`io::Write`] adapter returned by [`Escape::writer`]; escapes bytes
+/// written to it and forwards the result to `inner`. Any partial utf8
+/// sequence left over at the end of a `write` call is buffered and
+/// completed by a subsequent call, so multi-byte chars straddling a write
+/// boundary are escaped correctly.
+pub struct EscapeWriter<'e, W> {
+    esc: &'e Escape,
+    inner: W,
+    pending: Vec<u8>,
+}
+
+impl<'e, W: io::Write> EscapeWriter<'e, W> {
+    fn escape_and_forward(&mut self, s: &str) -> io::Result<()> {
+        let mut escaped = String::with_capacity(s.len());
+        self.esc.escape_to(s, &mut escaped);
+        self.inner.write_all(escaped.as_bytes())
+    }
+
+    /// declare the stream done and hand back `inner`. Returns an error if
+    /// `pending` is non-empty, meaning the stream ended in the middle of a
+    /// multi-byte utf8 sequence: those bytes can never be completed now, and
+    /// dropping the writer without calling this would otherwise discard them
+    /// silently.
+    pub fn finish(mut self) -> io::Result<W> {
+        io::Write::flush(&mut self)?;
+        if self.pending.is_empty() {
+            Ok(self.inner)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream ended with an incomplete utf8 sequence",
+            ))
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for EscapeWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // work out how much of `pending` plus `buf` is decodable without
+        // committing either to `self.pending` yet, so that if forwarding
+        // fails (a real error, or `WouldBlock` under non-blocking io)
+        // `self.pending` is left exactly as it was before this call: no
+        // pending bytes are lost, and if `inner` accepted none of this
+        // call's escaped output, `buf` can be retried later without
+        // duplicating anything
+        let mut combined = self.pending.clone();
+        combined.extend_from_slice(buf);
+        let decodable_len = match std::str::from_utf8(&combined) {
+            Ok(s) => s.len(),
+            Err(e) => match e.error_len() {
+                // an incomplete sequence at the end of the buffer may be
+                // completed by the next call to write
+                None => e.valid_up_to(),
+                // not just a boundary split, but genuinely invalid utf8
+                Some(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "stream did not contain valid utf8",
+                    ));
+                }
+            },
+        };
+        if decodable_len > 0 {
+            let s = std::str::from_utf8(&combined[..decodable_len])
+                .expect("decodable_len bytes are valid utf8");
+            self.escape_and_forward(s)?;
+        }
+        combined.drain(..decodable_len);
+        self.pending = combined;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// [`io::Read`] adapter returned by [`Escape::reader`]; reads escaped bytes
+/// from `inner` and yields them unescaped. Any bytes that might still be the
+/// start of an escape sequence are held back until either more bytes arrive
+/// to resolve them or `inner` reaches eof.
+pub struct UnescapeReader<'e, R> {
+    esc: &'e Escape,
+    inner: R,
+    raw: Vec<u8>,
+    out: Vec<u8>,
+    out_pos: usize,
+}
+
+impl<'e, R> UnescapeReader<'e, R> {
+    /// the longest possible escape sequence this `Escape` can produce on the
+    /// byte api: the escape char followed by either the longest translation
+    /// target or an `xHH` byte escape, whichever is longer
+    fn max_escape_len(&self) -> usize {
+        1 + self
+            .esc
+            .tr
+            .iter()
+            .map(|(_, v)| v.len())
+            .max()
+            .unwrap_or(0)
+            .max(3)
+    }
+
+    /// the prefix of `raw` that is safe to decode now: everything up to the
+    /// last escape char, unless that escape char already has enough trailing
+    /// bytes to resolve on its own or is itself escaped by the one before it
+    fn safe_len(&self) -> usize {
+        let escape_byte = self.esc.escape_char as u8;
+        match self.raw.iter().rposition(|&b| b == escape_byte) {
+            Some(idx) if self.raw.len() - idx < self.max_escape_len() => {
+                // an even run of escape chars ending at `idx` is all complete
+                // pairs, so nothing in it is waiting on more bytes
+                let run = self.raw[..=idx]
+                    .iter()
+                    .rev()
+                    .take_while(|&&b| b == escape_byte)
+                    .count();
+                if run % 2 == 0 { self.raw.len() } else { idx }
+            }
+            _ => self.raw.len(),
+        }
+    }
+}
+
+impl<R: io::Read> io::Read for UnescapeReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.out_pos >= self.out.len() {
+            self.out.clear();
+            self.out_pos = 0;
+            let mut chunk = [0u8; 4096];
+            let n = self.inner.read(&mut chunk)?;
+            self.raw.extend_from_slice(&chunk[..n]);
+            let safe_len = if n == 0 {
+                // eof: nothing more is coming, decode whatever is left
+                self.raw.len()
+            } else {
+                self.safe_len()
+            };
+            let decodable: Vec<u8> = self.raw.drain(..safe_len).collect();
+            self.esc.unescape_bytes_to(&decodable, &mut self.out);
+            if n == 0 {
+                break;
+            }
+        }
+        let n = buf.len().min(self.out.len() - self.out_pos);
+        buf[..n].copy_from_slice(&self.out[self.out_pos..self.out_pos + n]);
+        self.out_pos += n;
+        Ok(n)
+    }
+}
+
 impl Escape {
     /// return the escape char
     pub fn get_escape_char(&self) -> char {
@@ -40,6 +476,11 @@ impl Escape {
         &self.tr
     }
 
+    /// return the unicode escape format
+    pub fn get_unicode_format(&self) -> UnicodeFormat {
+        self.unicode_format
+    }
+
     /// Create a new Escape, return an error if the folowing invariants are violated
     /// - the escape array must contain the escape_char.
     /// - the escape array must contain every first char in tr
@@ -49,6 +490,7 @@ impl Escape {
     /// - translation targets must be unique
     /// - translation targets may not be empty
     /// - translation targets may not start with u
+    /// - translation targets may not start with U if `unicode_format` is `Fixed8`
     /// - translation targets may not contain the escape char
     ///
     /// `escape` is the list of characters that will be escaped when you call `escape`
@@ -60,11 +502,16 @@ impl Escape {
     ///
     /// `generic`, if specified, will be called for each char, if it returns true,
     /// then the character will be translated to it's unicode escape sequence
+    ///
+    /// `unicode_format` selects the textual form used for unicode escape
+    /// sequences, e.g. `\u{hex}` vs `\uXXXX` vs `\UXXXXXXXX`. See
+    /// [`UnicodeFormat`] for the available forms.
     pub fn new(
         escape_char: char,
         escape: &[char],
         tr: &[(char, &str)],
         generic: Option<fn(char) -> bool>,
+        unicode_format: UnicodeFormat,
     ) -> Result<Self> {
         if !escape_char.is_ascii() {
             bail!("the escape char must be ascii")
@@ -85,6 +532,9 @@ impl Escape {
             if s.starts_with("u") {
                 bail!("translation targets must not start with u")
             }
+            if unicode_format == UnicodeFormat::Fixed8 && s.starts_with("U") {
+                bail!("translation targets must not start with U")
+            }
             if s.contains(escape_char) {
                 bail!("translation targets may not contain the escape char")
             }
@@ -107,33 +557,75 @@ impl Escape {
             escape: Box::from(escape),
             tr: Box::from_iter(tr.iter().map(|(c, s)| (*c, CompactString::new(s)))),
             generic,
+            unicode_format,
         })
     }
 
-    /// Escape the string and place the results into the buffer
+    /// Escape the string and place the results into the buffer. Implemented
+    /// in terms of [`escape_iter`](Self::escape_iter).
     pub fn escape_to<T>(&self, s: &T, buf: &mut String)
     where
         T: AsRef<str> + ?Sized,
     {
-        for c in s.as_ref().chars() {
-            if self.escape.contains(&c) {
-                buf.push(self.escape_char);
-                match self
-                    .tr
-                    .iter()
-                    .find_map(|(s, e)| if c == *s { Some(e) } else { None })
-                {
-                    Some(e) => buf.push_str(e),
-                    None => buf.push(c),
-                }
-            } else if let Some(generic) = &self.generic
-                && (generic)(c)
-            {
-                use std::fmt::Write;
-                buf.push(self.escape_char);
-                write!(buf, "u{{{:x}}}", c as u32).unwrap();
-            } else {
-                buf.push(c);
+        buf.extend(self.escape_iter(s.as_ref()));
+    }
+
+    /// Escape the string lazily, yielding the escaped form one char at a
+    /// time without allocating a `String`. The iterator holds a small
+    /// [`PendingChars`] queue for the escape char plus whatever translation
+    /// or `u{...}` expansion follows it, and drains that before advancing
+    /// the source. `escape_to` is implemented on top of this.
+    pub fn escape_iter<'e, 'a, T>(
+        &'e self,
+        s: &'a T,
+    ) -> impl Iterator<Item = char> + use<'e, 'a, T>
+    where
+        T: AsRef<str> + ?Sized,
+    {
+        EscapeIter {
+            esc: self,
+            chars: s.as_ref().chars(),
+            pending: PendingChars::default(),
+        }
+    }
+
+    /// push a single unicode escape sequence for code point `n` onto
+    /// `pending`, including the leading escape char(s), in whichever
+    /// `UnicodeFormat` this `Escape` was configured with
+    fn push_unicode_escape(&self, pending: &mut PendingChars, n: u32) {
+        // lowercase hex digits of `n`, zero padded to at least `width`,
+        // pushed one at a time so no intermediate `String` is allocated
+        fn push_digits(pending: &mut PendingChars, n: u32, width: usize) {
+            let digits = (32 - n.leading_zeros() as usize).div_ceil(4).max(width);
+            for i in (0..digits).rev() {
+                let nibble = (n >> (i * 4)) & 0xF;
+                pending.push_back(char::from_digit(nibble, 16).expect("a nibble is a hex digit"));
+            }
+        }
+        fn push_hex(pending: &mut PendingChars, escape_char: char, marker: char, n: u32, width: usize) {
+            pending.push_back(escape_char);
+            pending.push_back(marker);
+            push_digits(pending, n, width);
+        }
+        match self.unicode_format {
+            UnicodeFormat::Braced => {
+                pending.push_back(self.escape_char);
+                pending.push_back('u');
+                pending.push_back('{');
+                push_digits(pending, n, 1);
+                pending.push_back('}');
+            }
+            UnicodeFormat::Fixed4 => push_hex(pending, self.escape_char, 'u', n, 4),
+            UnicodeFormat::Fixed8 => push_hex(pending, self.escape_char, 'U', n, 8),
+            UnicodeFormat::Fixed4WithSurrogatePairs if n > 0xFFFF => {
+                let n = n - 0x10000;
+                let high = 0xD800 + (n >> 10);
+                let low = 0xDC00 + (n & 0x3FF);
+                push_hex(pending, self.escape_char, 'u', high, 4);
+                push_hex(pending, self.escape_char, 'u', low, 4);
+            }
+            UnicodeFormat::Fixed4WithSurrogatePairs => {
+                push_hex(pending, self.escape_char, 'u', n, 4)
             }
         }
     }
@@ -162,61 +654,437 @@ impl Escape {
         }
     }
 
-    /// Unescape the string and place the result in the buffer.
+    /// parse a single unicode escape sequence at the start of `rest`,
+    /// honoring this `Escape`'s configured `UnicodeFormat`. Returns the number
+    /// of bytes of `rest` consumed and the decoded char, or `None` if `rest`
+    /// does not start with a well formed escape in that format.
+    fn parse_unicode_escape_seq(&self, rest: &str) -> Option<(usize, char)> {
+        match self.unicode_format {
+            UnicodeFormat::Braced => {
+                if !rest.starts_with("u{") {
+                    return None;
+                }
+                let i = rest.find('}')?;
+                let n = u32::from_str_radix(&rest[2..i], 16).ok()?;
+                let c = char::from_u32(n)?;
+                Some((i + 1, c))
+            }
+            UnicodeFormat::Fixed4 => {
+                if !rest.starts_with('u') || !rest.is_char_boundary(5) {
+                    return None;
+                }
+                let n = u32::from_str_radix(&rest[1..5], 16).ok()?;
+                let c = char::from_u32(n)?;
+                Some((5, c))
+            }
+            UnicodeFormat::Fixed8 => {
+                if !rest.starts_with('U') || !rest.is_char_boundary(9) {
+                    return None;
+                }
+                let n = u32::from_str_radix(&rest[1..9], 16).ok()?;
+                let c = char::from_u32(n)?;
+                Some((9, c))
+            }
+            UnicodeFormat::Fixed4WithSurrogatePairs => {
+                if !rest.starts_with('u') || !rest.is_char_boundary(5) {
+                    return None;
+                }
+                let n = u32::from_str_radix(&rest[1..5], 16).ok()?;
+                if (0xD800..=0xDBFF).contains(&n) {
+                    let tail = &rest[5..];
+                    if tail.as_bytes().first() != Some(&(self.escape_char as u8)) {
+                        return None;
+                    }
+                    let low_rest = &tail[1..];
+                    if !low_rest.starts_with('u') || !low_rest.is_char_boundary(5) {
+                        return None;
+                    }
+                    let low = u32::from_str_radix(&low_rest[1..5], 16).ok()?;
+                    if !(0xDC00..=0xDFFF).contains(&low) {
+                        return None;
+                    }
+                    let combined = 0x10000 + ((n - 0xD800) << 10) + (low - 0xDC00);
+                    let c = char::from_u32(combined)?;
+                    Some((5 + 1 + 5, c))
+                } else {
+                    let c = char::from_u32(n)?;
+                    Some((5, c))
+                }
+            }
+        }
+    }
+
+    /// Unescape the string and place the result in the buffer. Implemented
+    /// in terms of [`unescape_iter`](Self::unescape_iter).
     pub fn unescape_to<T>(&self, s: &T, buf: &mut String)
     where
         T: AsRef<str> + ?Sized,
     {
-        fn parse_unicode_escape_seq(s: &str) -> Option<(usize, char)> {
-            if !s.starts_with("u{") {
-                return None;
-            }
-            let i = s.find('}')?;
-            let n = u32::from_str_radix(&s[2..i], 16).ok()?;
-            let c = char::from_u32(n)?;
-            Some((i + 1, c))
+        buf.extend(self.unescape_iter(s.as_ref()));
+    }
+
+    /// Unescape the string lazily, yielding the unescaped form one char at a
+    /// time without allocating a `String`. `unescape_to` is implemented on
+    /// top of this.
+    pub fn unescape_iter<'e, 'a, T>(
+        &'e self,
+        s: &'a T,
+    ) -> impl Iterator<Item = char> + use<'e, 'a, T>
+    where
+        T: AsRef<str> + ?Sized,
+    {
+        UnescapeIter {
+            esc: self,
+            rest: s.as_ref(),
         }
+    }
+
+    /// Unescape the string, or return it unmodified if it did not need to be
+    /// unescaped
+    pub fn unescape<'a, T>(&self, s: &'a T) -> Cow<'a, str>
+    where
+        T: AsRef<str> + ?Sized,
+    {
+        let s = s.as_ref();
+        if !s.contains(self.escape_char) {
+            Cow::Borrowed(s.as_ref())
+        } else {
+            let mut res = String::with_capacity(s.len());
+            self.unescape_to(s, &mut res);
+            Cow::Owned(res)
+        }
+    }
+
+    /// Unescape the string and place the result in the buffer, or return an
+    /// error if the input contains a malformed escape sequence.
+    ///
+    /// Unlike [`unescape_to`](Self::unescape_to), this validates every escape
+    /// sequence instead of silently passing through garbage: a lone escape
+    /// char at the end of input, an unterminated or empty `\u{...}`, a non hex
+    /// digit inside the braces, or a code point that is out of range or a
+    /// lone surrogate are all reported as an [`UnescapeError`] carrying the
+    /// byte offset at which the bad sequence begins.
+    pub fn try_unescape_to<T>(&self, s: &T, buf: &mut String) -> Result<(), UnescapeError>
+    where
+        T: AsRef<str> + ?Sized,
+    {
+        let s = s.as_ref();
         let mut escaped = false;
+        let mut escape_pos = 0;
         let mut skip_to = 0;
-        let s = s.as_ref();
-        buf.extend(s.char_indices().filter_map(|(i, c)| {
+        let unicode_marker = match self.unicode_format {
+            UnicodeFormat::Fixed8 => 'U',
+            UnicodeFormat::Braced | UnicodeFormat::Fixed4 | UnicodeFormat::Fixed4WithSurrogatePairs => 'u',
+        };
+        for (i, c) in s.char_indices() {
             if i < skip_to {
-                None
-            } else if c == self.escape_char && !escaped {
+                continue;
+            }
+            if c == self.escape_char && !escaped {
                 escaped = true;
-                None
-            } else if escaped {
-                escaped = false;
-                for (v, k) in &self.tr {
-                    if s[i..].starts_with(k.as_str()) {
-                        skip_to = i + k.len();
-                        return Some(*v);
-                    }
+                escape_pos = i;
+                continue;
+            }
+            if !escaped {
+                buf.push(c);
+                continue;
+            }
+            escaped = false;
+            if let Some((v, k)) = self.tr.iter().find(|(_, k)| s[i..].starts_with(k.as_str())) {
+                skip_to = i + k.len();
+                buf.push(*v);
+                continue;
+            }
+            if c == unicode_marker {
+                let (len, ch) = self.try_parse_unicode_escape_seq(escape_pos, &s[i..])?;
+                skip_to = i + len;
+                buf.push(ch);
+                continue;
+            }
+            buf.push(c);
+        }
+        if escaped {
+            return Err(UnescapeError {
+                pos: escape_pos,
+                kind: UnescapeErrorKind::LoneEscapeChar,
+            });
+        }
+        Ok(())
+    }
+
+    /// fallible counterpart of [`parse_unicode_escape_seq`](Self::parse_unicode_escape_seq),
+    /// used by [`try_unescape_to`](Self::try_unescape_to). `rest` starts at
+    /// the unicode escape marker (`u` or `U`); `pos` is the byte offset of the
+    /// escape char that precedes it, used to anchor any reported error.
+    fn try_parse_unicode_escape_seq(
+        &self,
+        pos: usize,
+        rest: &str,
+    ) -> Result<(usize, char), UnescapeError> {
+        fn hex_digits(pos: usize, rest: &str, width: usize) -> Result<(u32, usize), UnescapeError> {
+            if rest.len() < width || !rest.is_char_boundary(width) {
+                return Err(UnescapeError {
+                    pos,
+                    kind: UnescapeErrorKind::UnclosedUnicodeEscape,
+                });
+            }
+            let n = u32::from_str_radix(&rest[..width], 16).map_err(|_| UnescapeError {
+                pos,
+                kind: UnescapeErrorKind::InvalidCharInUnicodeEscape,
+            })?;
+            Ok((n, width))
+        }
+        let code_point = |pos: usize, n: u32| -> Result<char, UnescapeError> {
+            if (0xD800..=0xDFFF).contains(&n) {
+                return Err(UnescapeError {
+                    pos,
+                    kind: UnescapeErrorKind::LoneSurrogateUnicodeEscape,
+                });
+            }
+            char::from_u32(n).ok_or(UnescapeError {
+                pos,
+                kind: UnescapeErrorKind::OutOfRangeUnicodeEscape,
+            })
+        };
+        match self.unicode_format {
+            UnicodeFormat::Braced => {
+                let after_u = &rest[1..];
+                if !after_u.starts_with('{') {
+                    return Err(UnescapeError {
+                        pos,
+                        kind: UnescapeErrorKind::NoBraceInUnicodeEscape,
+                    });
                 }
-                if let Some((j, c)) = parse_unicode_escape_seq(&s[i..]) {
-                    skip_to = i + j;
-                    return Some(c);
+                let inner = &after_u[1..];
+                let close = inner.find('}').ok_or(UnescapeError {
+                    pos,
+                    kind: UnescapeErrorKind::UnclosedUnicodeEscape,
+                })?;
+                let hex = &inner[..close];
+                if hex.is_empty() {
+                    return Err(UnescapeError {
+                        pos,
+                        kind: UnescapeErrorKind::EmptyUnicodeEscape,
+                    });
                 }
-                Some(c)
-            } else {
-                Some(c)
+                let n = u32::from_str_radix(hex, 16).map_err(|_| UnescapeError {
+                    pos,
+                    kind: UnescapeErrorKind::InvalidCharInUnicodeEscape,
+                })?;
+                let c = code_point(pos, n)?;
+                Ok((1 + 1 + hex.len() + 1, c))
+            }
+            UnicodeFormat::Fixed4 => {
+                let (n, len) = hex_digits(pos, &rest[1..], 4)?;
+                let c = code_point(pos, n)?;
+                Ok((1 + len, c))
+            }
+            UnicodeFormat::Fixed8 => {
+                let (n, len) = hex_digits(pos, &rest[1..], 8)?;
+                let c = code_point(pos, n)?;
+                Ok((1 + len, c))
+            }
+            UnicodeFormat::Fixed4WithSurrogatePairs => {
+                let (n, len) = hex_digits(pos, &rest[1..], 4)?;
+                if (0xDC00..=0xDFFF).contains(&n) {
+                    return Err(UnescapeError {
+                        pos,
+                        kind: UnescapeErrorKind::LoneSurrogateUnicodeEscape,
+                    });
+                }
+                if !(0xD800..=0xDBFF).contains(&n) {
+                    let c = code_point(pos, n)?;
+                    return Ok((1 + len, c));
+                }
+                let tail = &rest[1 + len..];
+                if tail.as_bytes().first() != Some(&(self.escape_char as u8)) {
+                    return Err(UnescapeError {
+                        pos,
+                        kind: UnescapeErrorKind::LoneSurrogateUnicodeEscape,
+                    });
+                }
+                let low_rest = &tail[1..];
+                if low_rest.as_bytes().first() != Some(&b'u') {
+                    return Err(UnescapeError {
+                        pos,
+                        kind: UnescapeErrorKind::LoneSurrogateUnicodeEscape,
+                    });
+                }
+                let (low, low_len) = hex_digits(pos, &low_rest[1..], 4)?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(UnescapeError {
+                        pos,
+                        kind: UnescapeErrorKind::LoneSurrogateUnicodeEscape,
+                    });
+                }
+                let combined = 0x10000 + ((n - 0xD800) << 10) + (low - 0xDC00);
+                let c = code_point(pos, combined)?;
+                Ok((1 + len + 1 + 1 + low_len, c))
             }
-        }))
+        }
     }
 
-    /// Unescape the string, or return it unmodified if it did not need to be
-    /// unescaped
-    pub fn unescape<'a, T>(&self, s: &'a T) -> Cow<'a, str>
+    /// Unescape the string, or return an error if it contains a malformed
+    /// escape sequence. See [`try_unescape_to`](Self::try_unescape_to) for the
+    /// validation this performs.
+    pub fn try_unescape<'a, T>(&self, s: &'a T) -> Result<Cow<'a, str>, UnescapeError>
     where
         T: AsRef<str> + ?Sized,
     {
         let s = s.as_ref();
         if !s.contains(self.escape_char) {
-            Cow::Borrowed(s.as_ref())
+            Ok(Cow::Borrowed(s))
         } else {
             let mut res = String::with_capacity(s.len());
-            self.unescape_to(s, &mut res);
-            Cow::Owned(res)
+            self.try_unescape_to(s, &mut res)?;
+            Ok(Cow::Owned(res))
+        }
+    }
+
+    /// Escape a byte slice and place the results into the buffer.
+    ///
+    /// This is the byte-oriented counterpart of [`escape_to`](Self::escape_to)
+    /// for input that isn't necessarily valid utf8. An ascii byte in `escape`
+    /// is escaped exactly as the equivalent char would be by `escape_to`.
+    /// Any other byte that needs escaping is escaped as `{escape_char}xHH`
+    /// (two hex digits), the way [`core::ascii::escape_default`] escapes
+    /// non-printable bytes, not as a unicode escape: an ascii byte when
+    /// `generic` returns true for it, and, if `generic` is configured, every
+    /// non-ascii byte, since it has no char to test against `escape`/`generic`.
+    pub fn escape_bytes_to(&self, b: &[u8], buf: &mut Vec<u8>) {
+        for &byte in b {
+            let as_char = byte.is_ascii().then_some(byte as char);
+            if as_char.is_some_and(|c| self.escape.contains(&c)) {
+                buf.push(self.escape_char as u8);
+                match self
+                    .tr
+                    .iter()
+                    .find_map(|(k, e)| if as_char == Some(*k) { Some(e) } else { None })
+                {
+                    Some(e) => buf.extend_from_slice(e.as_bytes()),
+                    None => buf.push(byte),
+                }
+            } else if let Some(generic) = &self.generic {
+                let escape_generically = match as_char {
+                    Some(c) => (generic)(c),
+                    None => true,
+                };
+                if escape_generically {
+                    buf.push(self.escape_char as u8);
+                    buf.push(b'x');
+                    buf.extend_from_slice(format!("{byte:02x}").as_bytes());
+                } else {
+                    buf.push(byte);
+                }
+            } else {
+                buf.push(byte);
+            }
+        }
+    }
+
+    /// Escape a byte slice, or return it unmodified if it did not need to be
+    /// escaped. See [`escape_bytes_to`](Self::escape_bytes_to).
+    pub fn escape_bytes<'a>(&self, b: &'a [u8]) -> Cow<'a, [u8]> {
+        let mut to_escape = 0;
+        for &byte in b {
+            let as_char = byte.is_ascii().then_some(byte as char);
+            let needs_escape = match as_char {
+                Some(c) => {
+                    self.escape.contains(&c)
+                        || self.generic.as_ref().map(|f| (f)(c)).unwrap_or(false)
+                }
+                None => self.generic.is_some(),
+            };
+            if needs_escape {
+                to_escape += 1;
+            }
+        }
+        if to_escape == 0 {
+            Cow::Borrowed(b)
+        } else {
+            let mut out = Vec::with_capacity(b.len() + to_escape);
+            self.escape_bytes_to(b, &mut out);
+            Cow::Owned(out)
+        }
+    }
+
+    /// Unescape a byte slice and place the result in the buffer. See
+    /// [`escape_bytes_to`](Self::escape_bytes_to) for the format this
+    /// expects.
+    pub fn unescape_bytes_to(&self, b: &[u8], buf: &mut Vec<u8>) {
+        let escape_byte = self.escape_char as u8;
+        let mut i = 0;
+        while i < b.len() {
+            let byte = b[i];
+            if byte != escape_byte {
+                buf.push(byte);
+                i += 1;
+                continue;
+            }
+            let rest = &b[i + 1..];
+            if rest.is_empty() {
+                buf.push(byte);
+                i += 1;
+                continue;
+            }
+            if let Some((k, len)) = self.tr.iter().find_map(|(k, e)| {
+                let e = e.as_bytes();
+                rest.starts_with(e).then_some((*k, e.len()))
+            }) {
+                let mut k_buf = [0u8; 4];
+                buf.extend_from_slice(k.encode_utf8(&mut k_buf).as_bytes());
+                i += 1 + len;
+                continue;
+            }
+            if rest.len() >= 3
+                && rest[0] == b'x'
+                && let Ok(hex) = std::str::from_utf8(&rest[1..3])
+                && let Ok(n) = u8::from_str_radix(hex, 16)
+            {
+                buf.push(n);
+                i += 1 + 3;
+                continue;
+            }
+            buf.push(rest[0]);
+            i += 2;
+        }
+    }
+
+    /// Unescape a byte slice, or return it unmodified if it did not need to
+    /// be unescaped. See [`unescape_bytes_to`](Self::unescape_bytes_to).
+    pub fn unescape_bytes<'a>(&self, b: &'a [u8]) -> Cow<'a, [u8]> {
+        if !b.contains(&(self.escape_char as u8)) {
+            Cow::Borrowed(b)
+        } else {
+            let mut out = Vec::with_capacity(b.len());
+            self.unescape_bytes_to(b, &mut out);
+            Cow::Owned(out)
+        }
+    }
+
+    /// wrap `inner` in an [`EscapeWriter`] that escapes bytes written to it
+    /// on the fly, buffering any partial utf8 sequence across `write` calls,
+    /// so large or incrementally produced data can be escaped straight into a
+    /// file, socket, or compressor without collecting an intermediate
+    /// `String`
+    pub fn writer<W: io::Write>(&self, inner: W) -> EscapeWriter<'_, W> {
+        EscapeWriter {
+            esc: self,
+            inner,
+            pending: Vec::new(),
+        }
+    }
+
+    /// wrap `inner` in an [`UnescapeReader`] that unescapes bytes read from
+    /// it on the fly. Uses the byte escape format understood by
+    /// [`unescape_bytes_to`](Self::unescape_bytes_to).
+    pub fn reader<R: io::Read>(&self, inner: R) -> UnescapeReader<'_, R> {
+        UnescapeReader {
+            esc: self,
+            inner,
+            raw: Vec::new(),
+            out: Vec::new(),
+            out_pos: 0,
         }
     }
 
@@ -259,6 +1127,29 @@ impl Escape {
         })
     }
 
+    /// like [`splitn`](Self::splitn), but each part is unescaped before it is
+    /// yielded, so callers don't need to call `unescape` on every field
+    /// themselves. Finds the unescaped separators and unescapes each field in
+    /// a single scan, the same way [`unescape_iter`](Self::unescape_iter)
+    /// does for a whole string.
+    pub fn splitn_unescaped<'e, 'a, T>(
+        &'e self,
+        s: &'a T,
+        n: usize,
+        sep: char,
+    ) -> impl Iterator<Item = Cow<'a, str>> + use<'e, 'a, T>
+    where
+        T: AsRef<str> + ?Sized,
+    {
+        SplitUnescapedIter {
+            esc: self,
+            rest: s.as_ref(),
+            sep,
+            remaining: Some(n),
+            finished: false,
+        }
+    }
+
     /// split the string into parts separated by non escaped instances of `sep`
     /// and return an iterator over the parts
     pub fn split<'a, T>(
@@ -275,4 +1166,27 @@ impl Escape {
             move |c| is_sep(&mut esc, escape_char, c, sep)
         })
     }
+
+    /// like [`split`](Self::split), but each part is unescaped before it is
+    /// yielded, so callers don't need to call `unescape` on every field
+    /// themselves. Finds the unescaped separators and unescapes each field in
+    /// a single scan, the same way [`unescape_iter`](Self::unescape_iter)
+    /// does for a whole string. Borrows when a field contains no escape char,
+    /// owns otherwise.
+    pub fn split_unescaped<'e, 'a, T>(
+        &'e self,
+        s: &'a T,
+        sep: char,
+    ) -> impl Iterator<Item = Cow<'a, str>> + use<'e, 'a, T>
+    where
+        T: AsRef<str> + ?Sized,
+    {
+        SplitUnescapedIter {
+            esc: self,
+            rest: s.as_ref(),
+            sep,
+            remaining: None,
+            finished: false,
+        }
+    }
 }
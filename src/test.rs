@@ -1,4 +1,4 @@
-use crate::Escape;
+use crate::{Escape, UnescapeErrorKind, UnicodeFormat};
 use proptest::prelude::*;
 use std::sync::LazyLock;
 
@@ -6,12 +6,20 @@ fn use_generic_escape(c: char) -> bool {
     c.is_control()
 }
 
+/// generic predicate for the json/toml-like fixtures: also escapes non ascii
+/// chars, so astral code points actually exercise the fixed-width unicode
+/// escape formats instead of passing through unescaped
+fn use_generic_unicode(c: char) -> bool {
+    c.is_control() || !c.is_ascii()
+}
+
 static ESC: LazyLock<Escape> = LazyLock::new(|| {
     Escape::new(
         '\\',
         &['\\', '[', ']', '"', '\0', '\n', '\r', '\t'],
         &[('\n', "n"), ('\r', "r"), ('\0', "0"), ('\t', "t")],
         Some(use_generic_escape),
+        UnicodeFormat::Braced,
     )
     .unwrap()
 });
@@ -35,7 +43,7 @@ proptest! {
     ) {
         let generic = if use_generic_flag { Some(use_generic_escape as fn(char) -> bool) } else { None };
         let tr: [(char, &str); 5] = std::array::from_fn(|i| (tr_keys[i], tr_values[i].as_str()));
-        if let Ok(esc) = Escape::new(escape_char, &escape, &tr, generic) {
+        if let Ok(esc) = Escape::new(escape_char, &escape, &tr, generic, UnicodeFormat::Braced) {
             let escaped = esc.escape(&input);
             let unescaped = esc.unescape(&escaped);
             assert_eq!(unescaped, input);
@@ -50,6 +58,7 @@ fn test_new_success() {
         &['\\', '[', ']', '"', '\0', '\n', '\r', '\t'],
         &[('\n', "n"), ('\r', "r"), ('\0', "0"), ('\t', "t")],
         Some(use_generic_escape),
+        UnicodeFormat::Braced,
     )
     .unwrap();
 }
@@ -61,6 +70,7 @@ fn test_new_fail_missing_escape_char() {
         &['[', ']', '"', '\0', '\n', '\r', '\t'],
         &[('\n', "n"), ('\r', "r"), ('\0', "0"), ('\t', "t")],
         None,
+        UnicodeFormat::Braced,
     );
     assert!(res.is_err());
 }
@@ -72,6 +82,7 @@ fn test_new_fail_duplicate_tr_key() {
         &['\\', '[', ']', '"', '\0', '\n', '\r', '\t'],
         &[('\n', "n"), ('\r', "r"), ('\0', "0"), ('\n', "t")], // duplicate key '\n'
         None,
+        UnicodeFormat::Braced,
     );
     assert!(res.is_err());
 }
@@ -83,6 +94,7 @@ fn test_new_fail_non_ascii_escape_char() {
         &['\\', '[', ']', '"', '\0', '\n', '\r', '\t'],
         &[('\n', "n"), ('\r', "r"), ('\0', "0"), ('\t', "t")],
         None,
+        UnicodeFormat::Braced,
     );
     assert!(res.is_err());
 }
@@ -94,6 +106,7 @@ fn test_new_fail_translate_escape_char() {
         &['\\', '[', ']', '"', '\0', '\n', '\r', '\t'],
         &[('\\', "esc"), ('\r', "r"), ('\0', "0"), ('\t', "t")],
         None,
+        UnicodeFormat::Braced,
     );
     assert!(res.is_err());
 }
@@ -105,6 +118,7 @@ fn test_new_fail_empty_translation_target() {
         &['\\', '[', ']', '"', '\0', '\n', '\r', '\t'],
         &[('\n', ""), ('\r', "r"), ('\0', "0"), ('\t', "t")],
         None,
+        UnicodeFormat::Braced,
     );
     assert!(res.is_err());
 }
@@ -116,6 +130,7 @@ fn test_new_fail_non_ascii_translation_target() {
         &['\\', '[', ']', '"', '\0', '\n', '\r', '\t'],
         &[('\n', "nñ"), ('\r', "r"), ('\0', "0"), ('\t', "t")],
         None,
+        UnicodeFormat::Braced,
     );
     assert!(res.is_err());
 }
@@ -127,6 +142,7 @@ fn test_new_fail_translation_starts_with_u() {
         &['\\', '[', ']', '"', '\0', '\n', '\r', '\t'],
         &[('\n', "uabc"), ('\r', "r"), ('\0', "0"), ('\t', "t")],
         None,
+        UnicodeFormat::Braced,
     );
     assert!(res.is_err());
 }
@@ -138,6 +154,7 @@ fn test_new_fail_translation_contains_escape() {
         &['\\', '[', ']', '"', '\0', '\n', '\r', '\t'],
         &[('\n', "n\\"), ('\r', "r"), ('\0', "0"), ('\t', "t")],
         None,
+        UnicodeFormat::Braced,
     );
     assert!(res.is_err());
 }
@@ -149,6 +166,7 @@ fn test_new_fail_key_not_in_escape() {
         &['\\', '[', ']', '"', '\0', '\r', '\t', 'x'],
         &[('\n', "n"), ('\r', "r"), ('\0', "0"), ('\t', "t")],
         None,
+        UnicodeFormat::Braced,
     );
     assert!(res.is_err());
 }
@@ -160,6 +178,7 @@ fn test_new_fail_duplicate_translation_target() {
         &['\\', '[', ']', '"', '\0', '\n', '\r', '\t'],
         &[('\n', "n"), ('\r', "n"), ('\0', "0"), ('\t', "t")],
         None,
+        UnicodeFormat::Braced,
     );
     assert!(res.is_err());
 }
@@ -224,3 +243,426 @@ fn test_splitn() {
     let parts: Vec<&str> = ESC.splitn(s, 3, ',').collect();
     assert_eq!(parts, vec!["a\\,b", "c\\,d"]);
 }
+
+#[test]
+fn test_split_unescaped() {
+    let s = "a\\,b,c\\nd";
+    let parts: Vec<std::borrow::Cow<str>> = ESC.split_unescaped(s, ',').collect();
+    assert_eq!(parts, vec!["a,b", "c\nd"]);
+}
+
+#[test]
+fn test_splitn_unescaped() {
+    let s = "a\\,b,c\\nd,e";
+    let parts: Vec<std::borrow::Cow<str>> = ESC.splitn_unescaped(s, 2, ',').collect();
+    assert_eq!(parts, vec!["a,b", "c\nd,e"]);
+}
+
+#[test]
+fn test_try_unescape_success() {
+    assert_eq!(
+        ESC.try_unescape(r#"foo \[e\] bar\n"#).unwrap(),
+        "foo [e] bar\n"
+    );
+    assert_eq!(ESC.try_unescape("foo bar").unwrap(), "foo bar");
+    let escaped = ESC.escape("control\u{1}").to_string();
+    assert_eq!(ESC.try_unescape(&escaped).unwrap(), "control\u{1}");
+}
+
+#[test]
+fn test_try_unescape_lone_escape_char() {
+    let err = ESC.try_unescape("foo\\").unwrap_err();
+    assert_eq!(err.pos, 3);
+    assert_eq!(err.kind, UnescapeErrorKind::LoneEscapeChar);
+}
+
+#[test]
+fn test_try_unescape_no_brace_in_unicode_escape() {
+    let err = ESC.try_unescape("\\u41").unwrap_err();
+    assert_eq!(err.pos, 0);
+    assert_eq!(err.kind, UnescapeErrorKind::NoBraceInUnicodeEscape);
+}
+
+#[test]
+fn test_try_unescape_unclosed_unicode_escape() {
+    let err = ESC.try_unescape("\\u{41").unwrap_err();
+    assert_eq!(err.pos, 0);
+    assert_eq!(err.kind, UnescapeErrorKind::UnclosedUnicodeEscape);
+}
+
+#[test]
+fn test_try_unescape_empty_unicode_escape() {
+    let err = ESC.try_unescape("\\u{}").unwrap_err();
+    assert_eq!(err.pos, 0);
+    assert_eq!(err.kind, UnescapeErrorKind::EmptyUnicodeEscape);
+}
+
+#[test]
+fn test_try_unescape_invalid_char_in_unicode_escape() {
+    let err = ESC.try_unescape("\\u{zz}").unwrap_err();
+    assert_eq!(err.pos, 0);
+    assert_eq!(err.kind, UnescapeErrorKind::InvalidCharInUnicodeEscape);
+}
+
+#[test]
+fn test_try_unescape_out_of_range_unicode_escape() {
+    let err = ESC.try_unescape("\\u{110000}").unwrap_err();
+    assert_eq!(err.pos, 0);
+    assert_eq!(err.kind, UnescapeErrorKind::OutOfRangeUnicodeEscape);
+}
+
+#[test]
+fn test_try_unescape_lone_surrogate_unicode_escape() {
+    let err = ESC.try_unescape("\\u{d800}").unwrap_err();
+    assert_eq!(err.pos, 0);
+    assert_eq!(err.kind, UnescapeErrorKind::LoneSurrogateUnicodeEscape);
+}
+
+fn json_like_escape() -> Escape {
+    Escape::new(
+        '\\',
+        &['\\', '"', '\n'],
+        &[('\n', "n")],
+        Some(use_generic_unicode),
+        UnicodeFormat::Fixed4WithSurrogatePairs,
+    )
+    .unwrap()
+}
+
+fn toml_like_escape() -> Escape {
+    Escape::new(
+        '\\',
+        &['\\', '"', '\n'],
+        &[('\n', "n")],
+        Some(use_generic_unicode),
+        UnicodeFormat::Fixed8,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_fixed4_round_trip() {
+    let esc = Escape::new(
+        '\\',
+        &['\\', '"'],
+        &[],
+        Some(use_generic_escape),
+        UnicodeFormat::Fixed4,
+    )
+    .unwrap();
+    let escaped = esc.escape("control\u{1}");
+    assert_eq!(escaped, "control\\u0001");
+    assert_eq!(esc.unescape(&escaped), "control\u{1}");
+}
+
+#[test]
+fn test_fixed8_round_trip() {
+    let esc = toml_like_escape();
+    let input = "emoji \u{1f600}";
+    let escaped = esc.escape(input);
+    assert_eq!(escaped, "emoji \\U0001f600");
+    assert_eq!(esc.unescape(&escaped), input);
+}
+
+#[test]
+fn test_fixed4_with_surrogate_pairs_round_trip() {
+    let esc = json_like_escape();
+    let input = "emoji \u{1f600}";
+    let escaped = esc.escape(input);
+    assert_eq!(escaped, "emoji \\ud83d\\ude00");
+    assert_eq!(esc.unescape(&escaped), input);
+}
+
+#[test]
+fn test_fixed4_with_surrogate_pairs_bmp_unchanged() {
+    let esc = json_like_escape();
+    let escaped = esc.escape("control\u{1}");
+    assert_eq!(escaped, "control\\u0001");
+}
+
+#[test]
+fn test_try_unescape_fixed4_with_surrogate_pairs() {
+    let esc = json_like_escape();
+    let err = esc.try_unescape("\\ud83d").unwrap_err();
+    assert_eq!(err.pos, 0);
+    assert_eq!(err.kind, UnescapeErrorKind::LoneSurrogateUnicodeEscape);
+    assert_eq!(
+        esc.try_unescape("\\ud83d\\ude00").unwrap(),
+        "\u{1f600}"
+    );
+}
+
+#[test]
+fn test_new_fail_translation_starts_with_u_uppercase_for_fixed8() {
+    let res = Escape::new(
+        '\\',
+        &['\\', '[', ']', '"', '\0', '\n', '\r', '\t'],
+        &[('\n', "Uabc"), ('\r', "r"), ('\0', "0"), ('\t', "t")],
+        None,
+        UnicodeFormat::Fixed8,
+    );
+    assert!(res.is_err());
+}
+
+#[test]
+fn test_escape_bytes_round_trip() {
+    let escaped = ESC.escape_bytes(b"foo [e] bar\n");
+    assert_eq!(&*escaped, &b"foo \\[e\\] bar\\n"[..]);
+    assert_eq!(&*ESC.unescape_bytes(&escaped), b"foo [e] bar\n");
+}
+
+#[test]
+fn test_escape_bytes_no_change() {
+    assert_eq!(&*ESC.escape_bytes(b"foo bar"), b"foo bar");
+    assert_eq!(&*ESC.unescape_bytes(b"foo bar"), b"foo bar");
+}
+
+#[test]
+fn test_escape_bytes_non_ascii_byte() {
+    // 0xff is not valid utf8 and has no corresponding char, but the generic
+    // predicate is configured, so it's always escaped as \xHH
+    let input: &[u8] = b"a\xffb";
+    let escaped = ESC.escape_bytes(input);
+    assert_eq!(&*escaped, &b"a\\xffb"[..]);
+    assert_eq!(&*ESC.unescape_bytes(&escaped), input);
+}
+
+#[test]
+fn test_escape_bytes_control_char() {
+    let input: &[u8] = b"a\x01b";
+    let escaped = ESC.escape_bytes(input);
+    assert_eq!(&*escaped, &b"a\\x01b"[..]);
+    assert_eq!(&*ESC.unescape_bytes(&escaped), input);
+}
+
+#[test]
+fn test_unescape_bytes_non_ascii_translation_key() {
+    // `Escape::new` only requires translation *targets* to be ascii; the key
+    // being translated may be any char, including non ascii ones
+    let esc = Escape::new(
+        '\\',
+        &['\\', 'π'],
+        &[('π', "pi")],
+        None,
+        UnicodeFormat::Braced,
+    )
+    .unwrap();
+    let escaped = esc.escape("π");
+    assert_eq!(escaped, "\\pi");
+    assert_eq!(&*esc.unescape_bytes(escaped.as_bytes()), "π".as_bytes());
+}
+
+#[test]
+fn test_escape_bytes_no_generic_leaves_non_ascii_alone() {
+    let esc = Escape::new(
+        '\\',
+        &['\\', '\n'],
+        &[('\n', "n")],
+        None,
+        UnicodeFormat::Braced,
+    )
+    .unwrap();
+    let input: &[u8] = b"a\xffb";
+    assert_eq!(&*esc.escape_bytes(input), input);
+}
+
+proptest! {
+    #[test]
+    fn prop_byte_round_trip(input in proptest::collection::vec(any::<u8>(), 0..64)) {
+        let escaped = ESC.escape_bytes(&input);
+        let unescaped = ESC.unescape_bytes(&escaped);
+        assert_eq!(&*unescaped, &input[..]);
+    }
+}
+
+#[test]
+fn test_escape_writer() {
+    use std::io::Write;
+
+    let mut out = Vec::new();
+    let mut w = ESC.writer(&mut out);
+    w.write_all("foo [e] bar\n".as_bytes()).unwrap();
+    w.flush().unwrap();
+    assert_eq!(out, b"foo \\[e\\] bar\\n");
+}
+
+/// a writer that fails the first time it's asked to accept any bytes, then
+/// accepts everything it's given afterwards, used to simulate a transient
+/// error (or non-blocking `WouldBlock`) from the inner writer
+struct FlakyWriter {
+    failed_once: bool,
+    out: Vec<u8>,
+}
+
+impl std::io::Write for FlakyWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.failed_once {
+            self.failed_once = true;
+            return Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "flaky"));
+        }
+        self.out.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_escape_writer_retries_after_inner_error_without_duplicating() {
+    use std::io::Write;
+
+    let mut inner = FlakyWriter {
+        failed_once: false,
+        out: Vec::new(),
+    };
+    let input = "foo [e] bar\n";
+    {
+        let mut w = ESC.writer(&mut inner);
+        // the first write fails before anything reaches `inner`; retrying
+        // with the same bytes must produce the correct output, not a
+        // duplicate or a short write
+        assert!(w.write(input.as_bytes()).is_err());
+        w.write_all(input.as_bytes()).unwrap();
+    }
+    assert_eq!(inner.out, b"foo \\[e\\] bar\\n");
+}
+
+#[test]
+fn test_escape_writer_split_multibyte_char() {
+    use std::io::Write;
+
+    // a control char that takes 2 utf8 bytes, written one byte at a time so
+    // the writer must buffer the partial sequence across calls
+    let input = "a\u{80}b";
+    let mut out = Vec::new();
+    let mut w = ESC.writer(&mut out);
+    for byte in input.as_bytes() {
+        w.write_all(&[*byte]).unwrap();
+    }
+    w.flush().unwrap();
+    assert_eq!(out, ESC.escape(input).as_bytes());
+}
+
+#[test]
+fn test_escape_writer_finish_returns_inner() {
+    use std::io::Write;
+
+    let mut out = Vec::new();
+    let mut w = ESC.writer(&mut out);
+    w.write_all("foo [e] bar\n".as_bytes()).unwrap();
+    w.finish().unwrap();
+    assert_eq!(out, b"foo \\[e\\] bar\\n");
+}
+
+#[test]
+fn test_escape_writer_finish_errors_on_incomplete_trailing_utf8() {
+    use std::io::Write;
+
+    // a lone lead byte of a 2-byte sequence, with its continuation byte
+    // never written: the writer has no way to know it's missing until told
+    // the stream is done
+    let mut out = Vec::new();
+    let mut w = ESC.writer(&mut out);
+    w.write_all(b"abc\xC2").unwrap();
+    assert!(w.finish().is_err());
+}
+
+#[test]
+fn test_unescape_reader() {
+    use std::io::Read;
+
+    let escaped = r#"foo \[e\] bar\n"#;
+    let mut r = ESC.reader(escaped.as_bytes());
+    let mut out = String::new();
+    r.read_to_string(&mut out).unwrap();
+    assert_eq!(out, "foo [e] bar\n");
+}
+
+#[test]
+fn test_unescape_reader_small_reads() {
+    use std::io::Read;
+
+    let escaped = r#"foo \[e\] bar\n"#;
+    let mut r = ESC.reader(escaped.as_bytes());
+    let mut out = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        match r.read(&mut byte).unwrap() {
+            0 => break,
+            n => out.extend_from_slice(&byte[..n]),
+        }
+    }
+    assert_eq!(out, b"foo [e] bar\n");
+}
+
+#[test]
+fn test_escape_iter_matches_escape() {
+    let input = "foo [e] bar\ncontrol\u{1}";
+    let iter: String = ESC.escape_iter(input).collect();
+    assert_eq!(iter, ESC.escape(input));
+}
+
+#[test]
+fn test_escape_iter_long_translation_target() {
+    // regression test: `PendingChars` must not be bounded by a fixed
+    // capacity, since `Escape::new` places no limit on translation target
+    // length
+    let long = "x".repeat(40);
+    let esc = Escape::new('\\', &['\\', '\n'], &[('\n', long.as_str())], None, UnicodeFormat::Braced).unwrap();
+    let input = "a\nb";
+    let expected = format!("a\\{long}b");
+    assert_eq!(esc.escape(input), expected);
+    let iter: String = esc.escape_iter(input).collect();
+    assert_eq!(iter, expected);
+}
+
+#[test]
+fn test_unescape_iter_matches_unescape() {
+    let input = r#"foo \[e\] bar\n\u{1}"#;
+    let iter: String = ESC.unescape_iter(input).collect();
+    assert_eq!(iter, ESC.unescape(input));
+}
+
+#[test]
+fn test_escape_iter_surrogate_pairs_matches_escape() {
+    let esc = json_like_escape();
+    let input = "emoji \u{1f600}";
+    let iter: String = esc.escape_iter(input).collect();
+    assert_eq!(iter, esc.escape(input));
+}
+
+#[test]
+fn test_unescape_iter_surrogate_pairs_matches_unescape() {
+    let esc = json_like_escape();
+    let escaped = "emoji \\ud83d\\ude00";
+    let iter: String = esc.unescape_iter(escaped).collect();
+    assert_eq!(iter, esc.unescape(escaped));
+}
+
+proptest! {
+    #[test]
+    fn prop_escape_iter_matches_escape_to(
+        escape_char in prop::char::range('\0', '\x7F'),
+        escape in prop::array::uniform10(any::<char>()),
+        tr_keys in prop::array::uniform5(any::<char>()),
+        tr_values in prop::array::uniform5(proptest::string::string_regex("[a-tv-zA-TV-Z0-9][a-zA-Z0-9]{0,4}").unwrap()),
+        use_generic_flag in any::<bool>(),
+        input in any::<String>(),
+    ) {
+        let generic = if use_generic_flag { Some(use_generic_escape as fn(char) -> bool) } else { None };
+        let tr: [(char, &str); 5] = std::array::from_fn(|i| (tr_keys[i], tr_values[i].as_str()));
+        if let Ok(esc) = Escape::new(escape_char, &escape, &tr, generic, UnicodeFormat::Braced) {
+            let mut escape_to_buf = String::new();
+            esc.escape_to(&input, &mut escape_to_buf);
+            let iter: String = esc.escape_iter(&input).collect();
+            assert_eq!(iter, escape_to_buf);
+
+            let mut unescape_to_buf = String::new();
+            esc.unescape_to(&escape_to_buf, &mut unescape_to_buf);
+            let iter: String = esc.unescape_iter(&escape_to_buf).collect();
+            assert_eq!(iter, unescape_to_buf);
+        }
+    }
+}